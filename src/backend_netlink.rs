@@ -0,0 +1,156 @@
+use std::{collections::BTreeMap, fs};
+
+use wireguard_uapi::{get, set, DeviceInterface, WgSocket};
+
+use crate::backend_tools::WgToolsBackend;
+use crate::interface::{WgBackend, WgInterface, WgPeer, WgSetEvent};
+
+/// Talks to the in-kernel WireGuard implementation directly over its
+/// generic-netlink family, bypassing the `wg`/`wg-quick` subprocesses
+/// entirely for device reads and writes.
+pub struct NetlinkBackend;
+
+impl NetlinkBackend {
+    /// Probes for the WireGuard netlink family and at least one device so
+    /// `main::detect_backend` can fall back to `WgToolsBackend` on kernels
+    /// without the module loaded, or when the family simply isn't reachable
+    /// (e.g. missing `CAP_NET_ADMIN`).
+    pub fn detect() -> Option<NetlinkBackend> {
+        let mut socket = WgSocket::connect().ok()?;
+        let name = list_device_names().into_iter().next()?;
+        socket.get_device(DeviceInterface::from_name(name)).ok()?;
+        Some(NetlinkBackend)
+    }
+}
+
+//the WireGuard netlink family has no concept of interface enumeration; the
+//kernel exposes up interfaces as normal network devices instead
+fn list_device_names() -> Vec<String> {
+    fs::read_dir("/sys/class/net/")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("wireguard").is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+}
+
+impl WgBackend for NetlinkBackend {
+    fn dump(&self) -> BTreeMap<String, WgInterface> {
+        let mut socket = WgSocket::connect().expect("failed to open WireGuard netlink socket");
+        let mut interfaces = BTreeMap::new();
+
+        for name in list_device_names() {
+            let device = match socket.get_device(DeviceInterface::from_name(name.clone())) {
+                Ok(device) => device,
+                Err(_) => continue,
+            };
+
+            interfaces.insert(
+                name,
+                WgInterface {
+                    enabled: true,
+                    show_priv: false,
+                    private_key: device.private_key.to_string(),
+                    public_key: device.public_key.to_string(),
+                    listen_port: device.listen_port,
+                    fwmark: match device.fwmark {
+                        0 => None,
+                        mark => Some(mark.to_string()),
+                    },
+                    address: Vec::new(),
+                    dns: Vec::new(),
+                    peers: device
+                        .peers
+                        .into_iter()
+                        .map(|peer| WgPeer {
+                            public_key: peer.public_key.to_string(),
+                            preshared_key: peer.preshared_key.map(|k| k.to_string()),
+                            endpoint: peer
+                                .endpoint
+                                .map(|addr| addr.to_string())
+                                .unwrap_or_default(),
+                            allowed_ips: peer
+                                .allowed_ips
+                                .into_iter()
+                                .map(|ip| ip.to_string())
+                                .collect::<Vec<String>>()
+                                .join(", "),
+                            latest_handshake: peer.last_handshake_time.as_secs(),
+                            transfer_rx: peer.rx_bytes,
+                            transfer_tx: peer.tx_bytes,
+                            persistent_keepalive: peer.persistent_keepalive_interval,
+                        })
+                        .collect(),
+                },
+            );
+        }
+
+        interfaces
+    }
+
+    fn set(&self, name: &str, events: &[WgSetEvent]) -> Result<(), String> {
+        let mut socket = WgSocket::connect().map_err(|e| e.to_string())?;
+        let mut device = set::Device::from_interface(DeviceInterface::from_name(name.to_string()));
+
+        for event in events {
+            match event {
+                WgSetEvent::PrivateKey(key) => device.private_key = Some(parse_key(key)?),
+                WgSetEvent::Fwmark(mark) => {
+                    device.fwmark = Some(mark.parse().map_err(|_| "invalid fwmark")?)
+                }
+                WgSetEvent::ListenPort(port) => device.listen_port = Some(*port),
+                WgSetEvent::UpdatePeer {
+                    public_key,
+                    preshared_key,
+                    endpoint,
+                    allowed_ips,
+                    persistent_keepalive,
+                } => {
+                    let mut peer = set::Peer::from_public_key(parse_key(public_key)?);
+                    if let Some(psk) = preshared_key {
+                        peer.preshared_key = Some(parse_key(psk)?);
+                    }
+                    if let Some(endpoint) = endpoint {
+                        peer.endpoint = Some(endpoint.parse().map_err(|_| "invalid endpoint")?);
+                    }
+                    if let Some(allowed_ips) = allowed_ips {
+                        peer.allowed_ips = allowed_ips
+                            .split(',')
+                            .map(|ip| ip.trim().parse().map_err(|_| "invalid allowed-ips".to_string()))
+                            .collect::<Result<Vec<_>, String>>()?;
+                    }
+                    if let Some(keepalive) = persistent_keepalive {
+                        peer.persistent_keepalive_interval = Some(*keepalive);
+                    }
+                    device.peers.push(peer);
+                }
+                WgSetEvent::RemovePeer(public_key) => {
+                    let mut peer = set::Peer::from_public_key(parse_key(public_key)?);
+                    peer.flags.push(set::WgPeerF::RemoveMe);
+                    device.peers.push(peer);
+                }
+                WgSetEvent::RemoveAllPeers => device.flags.push(set::WgDeviceF::ReplacePeers),
+            }
+        }
+
+        socket.set_device(device).map_err(|e| e.to_string())
+    }
+
+    //genetlink's `WG_CMD_SET_DEVICE` only configures the WireGuard device
+    //itself; it doesn't assign addresses, set up routes, or run the
+    //`PostUp`/`PostDown` hooks `wg-quick` does. Rather than reimplement that
+    //half of `wg-quick` here too, lean on the tools backend for it, the same
+    //way most native WireGuard reimplementations end up doing.
+    fn up(&self, name: &str) -> Result<(), String> {
+        WgToolsBackend.up(name)
+    }
+
+    fn down(&self, name: &str) -> Result<(), String> {
+        WgToolsBackend.down(name)
+    }
+}
+
+fn parse_key(key: &str) -> Result<get::Key, String> {
+    key.parse().map_err(|_| format!("invalid key: {}", key))
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    process::{exit, Command},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::interface::{WgBackend, WgInterface, WgPeer, WgSetEvent};
+
+/// Talks to WireGuard the way this TUI always has: by shelling out to the
+/// `wg`/`wg-quick` command-line tools.
+pub struct WgToolsBackend;
+
+impl WgBackend for WgToolsBackend {
+    fn dump(&self) -> BTreeMap<String, WgInterface> {
+        let mut interfaces: BTreeMap<String, WgInterface> = BTreeMap::new();
+        let result = Command::new("wg")
+            .arg("show")
+            .arg("all")
+            .arg("dump")
+            .output()
+            .expect("Command failure");
+        //guarentee that user has proper permissions and that another error hasnt occured
+        if !&result.status.success() {
+            eprint!("{}", String::from_utf8_lossy(&result.stderr));
+            exit(1);
+        }
+
+        let raw_output = String::from_utf8_lossy(&result.stdout);
+        let mut lines: Vec<&str> = raw_output.split("\n").collect::<Vec<&str>>();
+        //wireguard places a tab at the end which means that the last item the vector
+        //is an empty string. We pop that last value to make sure we only have our
+        //data in the string
+        lines.pop();
+
+        for (i, line) in lines.iter().enumerate() {
+            let line: Vec<&str> = line.split("\t").collect();
+            if line.len() == 5 {
+                interfaces.insert(
+                    line[0].to_string(),
+                    WgInterface {
+                        show_priv: false,
+                        enabled: true,
+                        private_key: line[1].to_string(),
+                        public_key: line[2].to_string(),
+                        listen_port: line[3]
+                            .parse()
+                            .expect("Value {line[3]} could not be parsed to listen_port(u16)"),
+                        fwmark: match line[4] {
+                            "off" => None,
+                            _ => Some(line[4].to_string()),
+                        },
+                        //`wg show dump` has no concept of addresses/DNS; those only
+                        //live in the interface's config file
+                        address: Vec::new(),
+                        dns: Vec::new(),
+                        //true fuckery. fill all the peers into their proper locations as long as the
+                        //peer shares a name with the interface
+                        peers: lines
+                            .iter()
+                            .skip(i + 1)
+                            .map(|x| x.split("\t").collect::<Vec<&str>>())
+                            .take_while(|x| line[0] == x[0])
+                            .map(|x| WgPeer {
+                                public_key: x[1].to_string(),
+                                preshared_key: match x[2] {
+                                    "(none)" => None,
+                                    _ => Some(x[2].to_string()),
+                                },
+                                endpoint: x[3].to_string(),
+                                allowed_ips: x[4].to_string(),
+                                latest_handshake: x[5].parse().expect(
+                                    "Value {x[5]} could not be parsed to latest_handshake(u64)",
+                                ),
+                                transfer_rx: x[6]
+                                    .parse()
+                                    .expect("Value {x[6]} could not be parsed to transfer_rx(u64)"),
+                                transfer_tx: x[7]
+                                    .parse()
+                                    .expect("Value {x[7]} could not be parsed to transfer_tx(u64)"),
+                                //`wg show dump` emits either `off` or the configured
+                                //keepalive interval as a plain integer, never the
+                                //literal word "on"
+                                persistent_keepalive: match x[8] {
+                                    "off" => 0,
+                                    value => value.parse().unwrap_or(0),
+                                },
+                            })
+                            .collect::<Vec<WgPeer>>(),
+                    },
+                );
+            }
+        }
+
+        interfaces
+    }
+
+    fn set(&self, name: &str, events: &[WgSetEvent]) -> Result<(), String> {
+        let mut args: Vec<String> = Vec::new();
+        let mut key_files: Vec<std::path::PathBuf> = Vec::new();
+
+        for event in events {
+            match event {
+                WgSetEvent::PrivateKey(key) => {
+                    let path = match write_key_tempfile(key) {
+                        Ok(path) => path,
+                        Err(err) => {
+                            cleanup_key_files(&key_files);
+                            return Err(format!("failed to stage private key: {}", err));
+                        }
+                    };
+                    args.push("private-key".to_string());
+                    args.push(path.to_string_lossy().into_owned());
+                    key_files.push(path);
+                }
+                WgSetEvent::Fwmark(mark) => {
+                    args.push("fwmark".to_string());
+                    args.push(mark.clone());
+                }
+                WgSetEvent::ListenPort(port) => {
+                    args.push("listen-port".to_string());
+                    args.push(port.to_string());
+                }
+                WgSetEvent::UpdatePeer {
+                    public_key,
+                    preshared_key,
+                    endpoint,
+                    allowed_ips,
+                    persistent_keepalive,
+                } => {
+                    args.push("peer".to_string());
+                    args.push(public_key.clone());
+                    if let Some(psk) = preshared_key {
+                        let path = match write_key_tempfile(psk) {
+                            Ok(path) => path,
+                            Err(err) => {
+                                cleanup_key_files(&key_files);
+                                return Err(format!("failed to stage preshared key: {}", err));
+                            }
+                        };
+                        args.push("preshared-key".to_string());
+                        args.push(path.to_string_lossy().into_owned());
+                        key_files.push(path);
+                    }
+                    if let Some(endpoint) = endpoint {
+                        args.push("endpoint".to_string());
+                        args.push(endpoint.clone());
+                    }
+                    if let Some(allowed_ips) = allowed_ips {
+                        args.push("allowed-ips".to_string());
+                        args.push(allowed_ips.clone());
+                    }
+                    if let Some(keepalive) = persistent_keepalive {
+                        args.push("persistent-keepalive".to_string());
+                        args.push(keepalive.to_string());
+                    }
+                }
+                WgSetEvent::RemovePeer(public_key) => {
+                    args.push("peer".to_string());
+                    args.push(public_key.clone());
+                    args.push("remove".to_string());
+                }
+                WgSetEvent::RemoveAllPeers => {
+                    let current_peers = self
+                        .dump()
+                        .remove(name)
+                        .map(|interface| interface.peers)
+                        .unwrap_or_default();
+                    for peer in current_peers {
+                        args.push("peer".to_string());
+                        args.push(peer.public_key);
+                        args.push("remove".to_string());
+                    }
+                }
+            }
+        }
+
+        let result = Command::new("wg")
+            .arg("set")
+            .arg(name)
+            .args(&args)
+            .output()
+            .expect("Command failure");
+
+        //keys never touch the command line, only short-lived 0600 files; clean them up
+        //regardless of whether `wg set` succeeded
+        cleanup_key_files(&key_files);
+
+        if !result.status.success() {
+            return Err(String::from_utf8_lossy(&result.stderr).into_owned());
+        }
+
+        Ok(())
+    }
+
+    fn up(&self, name: &str) -> Result<(), String> {
+        run_wg_quick("up", name)
+    }
+
+    fn down(&self, name: &str) -> Result<(), String> {
+        run_wg_quick("down", name)
+    }
+}
+
+fn run_wg_quick(action: &str, name: &str) -> Result<(), String> {
+    let result = Command::new("wg-quick")
+        .arg(action)
+        .arg(name)
+        .output()
+        .expect("Command failure");
+
+    if !result.status.success() {
+        return Err(String::from_utf8_lossy(&result.stderr).into_owned());
+    }
+
+    Ok(())
+}
+
+fn cleanup_key_files(paths: &[std::path::PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+static KEY_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+//`wg set` reads private/preshared keys from a file (or stdin) rather than argv,
+//so a key never shows up in `ps`. Stage it in a 0600 file under the system
+//temp dir and let the caller delete it once the command has run.
+fn write_key_tempfile(key: &str) -> std::io::Result<std::path::PathBuf> {
+    let id = KEY_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("wgtui-key-{}-{}", std::process::id(), id));
+    //create the file with 0600 perms atomically rather than create-then-chmod,
+    //which would leave a window where the key sits in a world-readable file
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?;
+    file.write_all(key.trim().as_bytes())?;
+    Ok(path)
+}
@@ -1,17 +1,31 @@
 use core::fmt;
 use std::{
     collections::BTreeMap,
-    fmt::{format, Debug, Error},
     fs,
-    process::{exit, Child, Command},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-
+/// Decouples `InterfacesMap` from any one way of talking to WireGuard.
+/// `backend_tools::WgToolsBackend` shells out to `wg`/`wg-quick`, the way
+/// this TUI always has; `backend_netlink::NetlinkBackend` talks to the
+/// kernel module directly over the WireGuard generic-netlink interface.
+pub trait WgBackend: Send + Sync {
+    /// Enumerates every interface the backend currently has up, fully
+    /// populated. Down interfaces are layered in separately from config
+    /// files by `InterfacesMap::refresh`, since neither backend can see a
+    /// tunnel that isn't running.
+    fn dump(&self) -> BTreeMap<String, WgInterface>;
+    fn set(&self, name: &str, events: &[WgSetEvent]) -> Result<(), String>;
+    fn up(&self, name: &str) -> Result<(), String>;
+    fn down(&self, name: &str) -> Result<(), String>;
+}
 
 pub struct InterfacesMap {
     pub interfaces: BTreeMap<String, WgInterface>,
     pub current_interface: String,
+    backend: Option<Box<dyn WgBackend>>,
 }
 
 impl InterfacesMap {
@@ -19,83 +33,25 @@ impl InterfacesMap {
         let interfacesmap: InterfacesMap = InterfacesMap {
             interfaces: BTreeMap::new(),
             current_interface: String::new(),
+            backend: None,
         };
         interfacesmap
     }
 
+    /// Plugs in the backend chosen at startup (see `main::detect_backend`).
+    pub fn set_backend(&mut self, backend: Box<dyn WgBackend>) {
+        self.backend = Some(backend);
+    }
+
+    fn backend(&self) -> &dyn WgBackend {
+        self.backend
+            .as_deref()
+            .expect("set_backend must be called before the interface map is used")
+    }
+
     pub fn refresh(&mut self) {
-        let mut interfaces: BTreeMap<String, WgInterface> = BTreeMap::new();
-        let result = Command::new("wg")
-            .arg("show")
-            .arg("all")
-            .arg("dump")
-            .output()
-            .expect("Command failure");
-        //guarentee that user has proper permissions and that another error hasnt occured
-        if !&result.status.success() {
-            eprint!("{}", String::from_utf8_lossy(&result.stderr));
-            exit(1);
-        }
+        let mut interfaces = self.backend().dump();
 
-        let raw_output = String::from_utf8_lossy(&result.stdout);
-        let mut lines: Vec<&str> = raw_output.split("\n").collect::<Vec<&str>>();
-        //wireguard places a tab at the end which means that the last item the vector
-        //is an empty string. We pop that last value to make sure we only have our
-        //data in the string
-        lines.pop();
-
-        for (i, line) in lines.iter().enumerate() {
-            let line: Vec<&str> = line.split("\t").collect();
-            if line.len() == 5 {
-                interfaces.insert(
-                    line[0].to_string(),
-                    WgInterface {
-                        show_priv: false,
-                        enabled: true,
-                        private_key: line[1].to_string(),
-                        public_key: line[2].to_string(),
-                        listen_port: line[3]
-                            .parse()
-                            .expect("Value {line[3]} could not be parsed to listen_port(u16)"),
-                        fwmark: match line[4] {
-                            "off" => None,
-                            _ => Some(line[4].to_string()),
-                        },
-                        //true fuckery. fill all the peers into their proper locations as long as the
-                        //peer shares a name with the interface
-                        peers: lines
-                            .iter()
-                            .skip(i + 1)
-                            .map(|x| x.split("\t").collect::<Vec<&str>>())
-                            .take_while(|x| line[0] == x[0])
-                            .map(|x| WgPeer {
-                                public_key: x[1].to_string(),
-                                preshared_key: match x[2] {
-                                    "(none)" => None,
-                                    _ => Some(x[2].to_string()),
-                                },
-                                endpoint: x[3].to_string(),
-                                allowed_ips: x[4].to_string(),
-                                latest_handshake: x[5].parse().expect(
-                                    "Value {x[5]} could not be parsed to latest_handshake(u64)",
-                                ),
-                                transfer_rx: x[6]
-                                    .parse()
-                                    .expect("Value {x[6]} could not be parsed to transfer_rx(u64)"),
-                                transfer_tx: x[7]
-                                    .parse()
-                                    .expect("Value {x[7]} could not be parsed to transfer_tx(u64)"),
-                                persistent_keepalive: match x[8] {
-                                    "off" => false,
-                                    "on" => true,
-                                    _ => unreachable!(),
-                                },
-                            })
-                            .collect::<Vec<WgPeer>>(),
-                    },
-                );
-            }
-        }
         let interfaces_down = fs::read_dir("/etc/wireguard/")
             .unwrap()
             .map(|x| x.unwrap().file_name().into_string().unwrap())
@@ -104,11 +60,52 @@ impl InterfacesMap {
             .filter(|x| !interfaces.contains_key(x))
             .collect::<Vec<String>>();
         for item in interfaces_down {
-            interfaces.insert(item, Default::default());
+            let interface = parse_conf_file(&format!("/etc/wireguard/{}.conf", item));
+            interfaces.insert(item, interface);
         }
 
         self.interfaces = interfaces;
     }
+
+    /// Apply a batch of pending `WgSetEvent`s to `name`, then refresh state
+    /// from the backend. Mirrors the way userspace WireGuard's UAPI folds a
+    /// sequence of config operations into a single device update.
+    pub fn apply(&mut self, name: &str, events: &[WgSetEvent]) -> Result<(), String> {
+        self.backend().set(name, events)?;
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn up(&mut self, name: &str) -> Result<(), String> {
+        self.backend().up(name)?;
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn down(&mut self, name: &str) -> Result<(), String> {
+        self.backend().down(name)?;
+        self.refresh();
+        Ok(())
+    }
+}
+
+/// A single pending mutation to an interface or one of its peers, mirroring
+/// the update operations userspace WireGuard's UAPI exposes (set-device's
+/// private key/fwmark/listen-port fields and per-peer add/update/remove).
+#[derive(Debug, Clone)]
+pub enum WgSetEvent {
+    PrivateKey(String),
+    Fwmark(String),
+    ListenPort(u16),
+    UpdatePeer {
+        public_key: String,
+        preshared_key: Option<String>,
+        endpoint: Option<String>,
+        allowed_ips: Option<String>,
+        persistent_keepalive: Option<u16>,
+    },
+    RemovePeer(String),
+    RemoveAllPeers,
 }
 
 #[derive(Debug)]
@@ -120,6 +117,8 @@ pub struct WgInterface {
     pub fwmark: Option<String>,
     pub peers: Vec<WgPeer>,
     pub show_priv: bool,
+    pub address: Vec<String>,
+    pub dns: Vec<String>,
 }
 impl WgInterface {
     pub fn toggle_privkey(&mut self){
@@ -136,45 +135,202 @@ impl Default for WgInterface {
             fwmark: None,
             peers: Vec::new(),
             show_priv: false,
+            address: Vec::new(),
+            dns: Vec::new(),
         }
     }
 }
 
 impl fmt::Display for WgInterface {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Status: {}\n", if self.enabled { "up" } else { "down" })?;
+
         //guarentees that private key is only shown if the user has
         //decided to let it
-        if !self.enabled {
-            write!(f, "Interface is down.")
+        let private_key = if self.show_priv {
+            self.private_key.to_owned()
         } else {
-            let private_key = if self.show_priv {
-                self.private_key.to_owned()
+            String::from("(hidden)")
+        };
+        write!(f, "Private Key: {}\n", private_key)?;
+        write!(f, "Public Key: {}\n", self.public_key)?;
+        write!(f, "Listen Port: {}\n", self.listen_port)?;
+        write!(
+            f,
+            "fwmark: {}\n",
+            self.fwmark.to_owned().unwrap_or("off".to_string())
+        )?;
+        if !self.address.is_empty() {
+            write!(f, "Address: {}\n", self.address.join(", "))?;
+        }
+        if !self.dns.is_empty() {
+            write!(f, "DNS: {}\n", self.dns.join(", "))?;
+        }
+        write!(f, "----- Peers -----\n")?;
+
+        //display all the peers in the vector
+        for peer in self.peers.iter() {
+            write!(f, "{}", peer)?;
+            //seperate multiple peers
+            write!(f, "\n")?;
+        }
+        write!(f, " ")
+    }
+}
+
+//reads a wireguard-tools style `[Interface]`/`[Peer]` config file, the same
+//format `wg-quick` consumes, so a down interface can show what it would
+//connect to once brought up
+fn parse_conf_file(path: &str) -> WgInterface {
+    let mut interface = WgInterface::default();
+    let mut current_peer: Option<WgPeer> = None;
+    let mut section = "";
+
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    for line in contents.lines() {
+        let line = match line.find('#').map(|i| &line[..i]).unwrap_or(line).trim() {
+            "" => continue,
+            line => line,
+        };
+
+        if line.starts_with('[') && line.ends_with(']') {
+            if let Some(peer) = current_peer.take() {
+                interface.peers.push(peer);
+            }
+            section = if line.eq_ignore_ascii_case("[Peer]") {
+                current_peer = Some(WgPeer::default());
+                "Peer"
             } else {
-                String::from("(hidden)")
+                "Interface"
             };
-            write!(f, "Private Key: {}\n", private_key)?;
-            write!(f, "Public Key: {}\n", self.public_key)?;
-            write!(f, "Listen Port: {}\n", self.listen_port)?;
-            write!(
-                f,
-                "fwmark: {}\n",
-                self.fwmark.to_owned().unwrap_or("off".to_string())
-            )?;
-            write!(f, "----- Peers -----\n")?;
-
-            //display all the peers in the vector
-            for peer in self.peers.iter() {
-                write!(f, "{}", peer)?;
-                //seperate multiple peers
-                write!(f, "\n")?;
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim();
+
+        match section {
+            "Interface" => match key.to_lowercase().as_str() {
+                "privatekey" => interface.private_key = value.to_string(),
+                "listenport" => interface.listen_port = value.parse().unwrap_or_default(),
+                "fwmark" => interface.fwmark = Some(value.to_string()),
+                "address" => interface
+                    .address
+                    .extend(value.split(',').map(|s| s.trim().to_string())),
+                "dns" => interface
+                    .dns
+                    .extend(value.split(',').map(|s| s.trim().to_string())),
+                _ => {}
+            },
+            "Peer" => {
+                let peer = current_peer.get_or_insert_with(WgPeer::default);
+                match key.to_lowercase().as_str() {
+                    "publickey" => peer.public_key = value.to_string(),
+                    "presharedkey" => peer.preshared_key = Some(value.to_string()),
+                    "endpoint" => peer.endpoint = value.to_string(),
+                    "allowedips" => peer.allowed_ips = value.to_string(),
+                    "persistentkeepalive" => {
+                        peer.persistent_keepalive = if value.eq_ignore_ascii_case("off") {
+                            0
+                        } else {
+                            value.parse().unwrap_or(0)
+                        }
+                    }
+                    _ => {}
+                }
             }
-            write!(f, " ")
+            _ => {}
         }
     }
+    if let Some(peer) = current_peer.take() {
+        interface.peers.push(peer);
+    }
+
+    interface
 }
 
-#[derive(Debug)]
-struct WgPeer {
+/// Everything needed to scaffold a brand-new `/etc/wireguard/<name>.conf`,
+/// collected by the creation wizard before anything touches disk.
+pub struct NewInterfaceSpec {
+    pub name: String,
+    pub private_key: String,
+    pub listen_port: u16,
+    pub address: Vec<String>,
+    pub dns: Vec<String>,
+    pub peer: Option<NewPeerSpec>,
+}
+
+pub struct NewPeerSpec {
+    pub public_key: String,
+    pub endpoint: String,
+    pub allowed_ips: String,
+}
+
+/// Whether `name` is safe to use as an interface name and, by extension, as
+/// the `<name>` in `/etc/wireguard/<name>.conf` -- matches the kernel's
+/// `IFNAMSIZ` limit and the charset `wg-quick` itself restricts interface
+/// names to.
+pub fn valid_interface_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 15
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '=' | '+' | '.' | '-'))
+}
+
+/// Writes a well-formed wireguard-tools config file for a freshly scaffolded
+/// interface. Mirrors the layout `parse_conf_file` reads back.
+pub fn write_interface_conf(spec: &NewInterfaceSpec) -> std::io::Result<()> {
+    if !valid_interface_name(&spec.name) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid interface name: {}", spec.name),
+        ));
+    }
+
+    let mut contents = String::new();
+    contents += "[Interface]\n";
+    contents += &format!("PrivateKey = {}\n", spec.private_key);
+    if spec.listen_port != 0 {
+        contents += &format!("ListenPort = {}\n", spec.listen_port);
+    }
+    if !spec.address.is_empty() {
+        contents += &format!("Address = {}\n", spec.address.join(", "));
+    }
+    if !spec.dns.is_empty() {
+        contents += &format!("DNS = {}\n", spec.dns.join(", "));
+    }
+
+    if let Some(peer) = &spec.peer {
+        contents += "\n[Peer]\n";
+        contents += &format!("PublicKey = {}\n", peer.public_key);
+        if !peer.endpoint.is_empty() {
+            contents += &format!("Endpoint = {}\n", peer.endpoint);
+        }
+        let allowed_ips = if peer.allowed_ips.is_empty() {
+            "0.0.0.0/0"
+        } else {
+            &peer.allowed_ips
+        };
+        contents += &format!("AllowedIPs = {}\n", allowed_ips);
+    }
+
+    //contains a private key, so create the file locked down to 0600 up
+    //front rather than writing it world-readable and chmod'ing after
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(format!("/etc/wireguard/{}.conf", spec.name))?;
+    file.write_all(contents.as_bytes())
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct WgPeer {
     pub public_key: String,
     pub preshared_key: Option<String>,
     pub endpoint: String,
@@ -182,7 +338,9 @@ struct WgPeer {
     pub latest_handshake: u64,
     pub transfer_rx: u64,
     pub transfer_tx: u64,
-    pub persistent_keepalive: bool,
+    //seconds between keepalive packets; 0 means disabled, matching how
+    //`wg show dump`/wg-quick configs represent it
+    pub persistent_keepalive: u16,
 }
 
 impl fmt::Display for WgPeer {
@@ -213,12 +371,20 @@ impl fmt::Display for WgPeer {
             "Transfer: {} B recieved, {} B sent\n",
             self.transfer_rx, self.transfer_tx
         )?;
-        write!(f, "Persistent Keepalive: {}\n", self.persistent_keepalive)
+        write!(
+            f,
+            "Persistent Keepalive: {}\n",
+            if self.persistent_keepalive == 0 {
+                "off".to_string()
+            } else {
+                format!("{}s", self.persistent_keepalive)
+            }
+        )
     }
 }
 
 //TODO: improve this
-fn time_to_english(mut time: u64) -> Result<String, fmt::Error> {
+pub fn time_to_english(mut time: u64) -> Result<String, fmt::Error> {
     let mut output = String::new();
     let mut days = 0;
     let mut hours = 0;
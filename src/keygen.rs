@@ -0,0 +1,29 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rand::{rngs::OsRng, RngCore};
+use x25519_dalek::{x25519, X25519_BASEPOINT_BYTES};
+
+/// A freshly generated WireGuard keypair, base64-encoded the same way
+/// `wg genkey`/`wg pubkey` present theirs.
+pub struct WgKeypair {
+    pub private_key: String,
+    pub public_key: String,
+}
+
+/// Generates a Curve25519 keypair the same way `wg genkey` does: 32 random
+/// bytes, clamped per RFC 7748, with the public key derived by multiplying
+/// the clamped scalar with the Curve25519 base point.
+pub fn generate_keypair() -> WgKeypair {
+    let mut private = [0u8; 32];
+    OsRng.fill_bytes(&mut private);
+
+    private[0] &= 248;
+    private[31] &= 127;
+    private[31] |= 64;
+
+    let public = x25519(private, X25519_BASEPOINT_BYTES);
+
+    WgKeypair {
+        private_key: STANDARD.encode(private),
+        public_key: STANDARD.encode(public),
+    }
+}
@@ -15,23 +15,97 @@
  *   along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+mod backend_netlink;
+mod backend_tools;
 mod interface;
+mod keygen;
+mod monitor;
 
 use crate::interface::*;
 use cursive::{
     Cursive,
+    theme::{BaseColor, Color},
     traits::Nameable,
-    views::{Button, Dialog, DummyView, LinearLayout, SelectView, TextView},
+    utils::markup::StyledString,
+    views::{Button, Dialog, DummyView, EditView, LinearLayout, SelectView, TextView},
 };
 use parking_lot::RwLock;
-use std::process::Command;
+use std::{
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 static INTERFACES: RwLock<InterfacesMap> = RwLock::new(InterfacesMap::new());
+//mutations collected by the edit subsystem before the user hits "Apply"
+static PENDING_EVENTS: RwLock<Vec<WgSetEvent>> = RwLock::new(Vec::new());
+//per-peer throughput history, sampled on every refresh (manual or background)
+static MONITOR: RwLock<monitor::Monitor> = RwLock::new(monitor::Monitor::new());
+//answers collected by the "new interface" wizard, one step at a time
+static NEW_INTERFACE: RwLock<NewInterfaceDraft> = RwLock::new(NewInterfaceDraft {
+    name: String::new(),
+    listen_port: String::new(),
+    address: String::new(),
+    dns: String::new(),
+    peer_public_key: String::new(),
+    peer_endpoint: String::new(),
+    peer_allowed_ips: String::new(),
+});
+
+#[derive(Clone)]
+struct NewInterfaceDraft {
+    name: String,
+    listen_port: String,
+    address: String,
+    dns: String,
+    peer_public_key: String,
+    peer_endpoint: String,
+    peer_allowed_ips: String,
+}
+
+//prefers talking to the kernel directly over netlink, falling back to the
+//`wg`/`wg-quick` subprocesses this TUI has always used when the netlink
+//family isn't reachable (module not loaded, missing capabilities, etc.)
+fn detect_backend() -> Box<dyn WgBackend> {
+    match backend_netlink::NetlinkBackend::detect() {
+        Some(backend) => Box::new(backend),
+        None => Box::new(backend_tools::WgToolsBackend),
+    }
+}
+
+//feeds a fresh (transfer_rx, transfer_tx) sample into MONITOR for every peer
+//INTERFACES currently knows about. Call this right after INTERFACES actually
+//refreshes from the backend, never from a plain UI redraw -- sampling the
+//same cumulative counters at a new Instant produces spurious ~0 B/s entries
+//that flatten the sparklines.
+fn sample_monitor() {
+    let mut monitor = MONITOR.write();
+    for interface in INTERFACES.read().interfaces.values() {
+        for peer in &interface.peers {
+            monitor.record(&peer.public_key, peer.transfer_rx, peer.transfer_tx);
+        }
+    }
+}
 
 fn main() {
     let mut siv = cursive::default();
     main_menu(&mut siv);
+    INTERFACES.write().set_backend(detect_backend());
     INTERFACES.write().refresh();
+    sample_monitor();
+
+    //background polling: re-read the backend on a timer and push the refreshed
+    //details view to whichever screen is currently open
+    siv.set_autorefresh(true);
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        INTERFACES.write().refresh();
+        sample_monitor();
+        if cb_sink.send(Box::new(refresh_details_view)).is_err() {
+            break;
+        }
+    });
+
     siv.run();
 }
 
@@ -41,8 +115,9 @@ fn main_menu(s: &mut Cursive) {
     s.add_global_callback('q', |s| s.quit());
     let buttons = LinearLayout::vertical()
         .child(Button::new("list", list_connections))
-        .child(Button::new("edit", Cursive::quit))
+        .child(Button::new("edit", edit_connections))
         .child(Button::new("Activate", Cursive::quit))
+        .child(Button::new("New Interface", new_interface_wizard))
         .child(DummyView)
         .child(Button::new("Quit", Cursive::quit));
     s.add_layer(
@@ -53,12 +128,16 @@ fn main_menu(s: &mut Cursive) {
 fn list_connections(s: &mut Cursive) {
     s.pop_layer();
     INTERFACES.write().refresh();
+    sample_monitor();
     let details = TextView::new("").with_name("details");
     let interface_list = SelectView::<String>::new()
         //map all interface keys(names) into my SelectView
         .with_all_str(INTERFACES.read().interfaces.keys())
         .on_select(|s, item| {
-            let content = format!("{}", INTERFACES.read().interfaces.get(item).unwrap());
+            let buffer = INTERFACES.read();
+            let interface = buffer.interfaces.get(item).unwrap();
+            let content = render_interface_details(interface, &MONITOR.read());
+            drop(buffer);
             s.call_on_name("details", |v: &mut TextView| {
                 v.set_content(content);
             })
@@ -89,6 +168,98 @@ fn list_connections(s: &mut Cursive) {
     );
 }
 
+//redraws the "details" TextView of whichever screen has one open; fired once
+//a second by the background polling thread, so it must never panic on a
+//missing view
+fn refresh_details_view(s: &mut Cursive) {
+    let name = INTERFACES.read().current_interface.clone();
+    let buffer = INTERFACES.read();
+    let Some(interface) = buffer.interfaces.get(&name) else {
+        return;
+    };
+    let content = render_interface_details(interface, &MONITOR.read());
+    drop(buffer);
+    s.call_on_name("details", |v: &mut TextView| {
+        v.set_content(content);
+    });
+}
+
+//same information as `WgInterface`/`WgPeer`'s `Display` impls, plus the
+//bandwidth sparklines and a handshake freshness color cue that only make
+//sense in a live view
+fn render_interface_details(interface: &WgInterface, monitor: &monitor::Monitor) -> StyledString {
+    let mut content = StyledString::plain(format!(
+        "Status: {}\n",
+        if interface.enabled { "up" } else { "down" }
+    ));
+    let private_key = if interface.show_priv {
+        interface.private_key.clone()
+    } else {
+        String::from("(hidden)")
+    };
+    content.append_plain(format!("Private Key: {}\n", private_key));
+    content.append_plain(format!("Public Key: {}\n", interface.public_key));
+    content.append_plain(format!("Listen Port: {}\n", interface.listen_port));
+    content.append_plain(format!(
+        "fwmark: {}\n",
+        interface.fwmark.clone().unwrap_or("off".to_string())
+    ));
+    if !interface.address.is_empty() {
+        content.append_plain(format!("Address: {}\n", interface.address.join(", ")));
+    }
+    if !interface.dns.is_empty() {
+        content.append_plain(format!("DNS: {}\n", interface.dns.join(", ")));
+    }
+    content.append_plain("----- Peers -----\n");
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    for peer in &interface.peers {
+        content.append_plain(format!("Public Key: {}\n", peer.public_key));
+        content.append_plain(format!(
+            "Preshared Key: {}\n",
+            peer.preshared_key.clone().unwrap_or(String::from("(none)"))
+        ));
+        content.append_plain(format!("Endpoint: {}\n", peer.endpoint));
+        content.append_plain(format!("Allowed Ips: {}\n", peer.allowed_ips));
+
+        let time_since = now.saturating_sub(peer.latest_handshake);
+        let fresh = monitor::handshake_is_fresh(time_since, peer.persistent_keepalive);
+        content.append_plain("Latest handshake: ");
+        content.append_styled(
+            time_to_english(time_since).unwrap_or_default(),
+            if fresh {
+                Color::Dark(BaseColor::Green)
+            } else {
+                Color::Dark(BaseColor::Red)
+            },
+        );
+        content.append_plain("\n");
+
+        let (rx_line, tx_line) = monitor.render(&peer.public_key);
+        content.append_plain(format!(
+            "Transfer: {} B recieved, {} B sent\n",
+            peer.transfer_rx, peer.transfer_tx
+        ));
+        content.append_plain(format!("  RX {}\n", rx_line));
+        content.append_plain(format!("  TX {}\n", tx_line));
+        content.append_plain(format!(
+            "Persistent Keepalive: {}\n",
+            if peer.persistent_keepalive == 0 {
+                "off".to_string()
+            } else {
+                format!("{}s", peer.persistent_keepalive)
+            }
+        ));
+        content.append_plain("\n");
+    }
+
+    content
+}
+
 fn refresh_list(s: &mut Cursive) {
     s.pop_layer();
     list_connections(s);
@@ -129,15 +300,20 @@ fn interface_select(s: &mut Cursive, name: &str) {
 }
 
 fn change_state(s: &mut Cursive) {
-    let name = &INTERFACES.read().current_interface;
-    let enabled = INTERFACES.read().interfaces.get(name).unwrap().enabled;
-    let result = Command::new("wg-quick")
-        .arg(if enabled { "down" } else { "up" })
-        .arg(name.as_str())
-        .output()
-        .expect("Command failure");
-
-    let popup = Dialog::text(String::from_utf8_lossy(&result.stderr))
+    let name = INTERFACES.read().current_interface.clone();
+    let enabled = INTERFACES.read().interfaces.get(&name).unwrap().enabled;
+    let result = if enabled {
+        INTERFACES.write().down(&name)
+    } else {
+        INTERFACES.write().up(&name)
+    };
+
+    //up/down only refresh INTERFACES when they succeed
+    if result.is_ok() {
+        sample_monitor();
+    }
+    let message = result.err().unwrap_or_default();
+    let popup = Dialog::text(message)
         .button("OK", pop)
         .title(format!("Command output for {}", name));
     s.add_layer(popup);
@@ -147,3 +323,431 @@ fn change_state(s: &mut Cursive) {
 fn pop(s: &mut Cursive) {
     s.pop_layer();
 }
+
+fn edit_connections(s: &mut Cursive) {
+    s.pop_layer();
+    INTERFACES.write().refresh();
+    sample_monitor();
+    let interface_list = SelectView::<String>::new()
+        .with_all_str(INTERFACES.read().interfaces.keys())
+        .on_submit(edit_select);
+    let buttons = LinearLayout::horizontal().child(Button::new("Back", ret2main));
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(interface_list)
+                .child(DummyView)
+                .child(buttons),
+        )
+        .title("Edit an interface"),
+    );
+}
+
+fn edit_select(s: &mut Cursive, name: &str) {
+    INTERFACES.write().current_interface = name.to_string();
+    PENDING_EVENTS.write().clear();
+    draw_edit_menu(s);
+}
+
+//redraws the edit screen from INTERFACES/PENDING_EVENTS; every mutation below
+//goes through this so the peer list and pending count stay in sync
+fn draw_edit_menu(s: &mut Cursive) {
+    s.pop_layer();
+    let name = INTERFACES.read().current_interface.clone();
+    let buffer = INTERFACES.read();
+    let interface = buffer.interfaces.get(&name).unwrap();
+
+    let mut peer_list = SelectView::<String>::new();
+    peer_list.add_all_str(interface.peers.iter().map(|p| p.public_key.clone()));
+    drop(buffer);
+    peer_list.set_on_submit(edit_peer);
+
+    let pending = PENDING_EVENTS.read().len();
+    let buttons = LinearLayout::vertical()
+        .child(Button::new("Set Listen Port", edit_listen_port))
+        .child(Button::new("Set Fwmark", edit_fwmark))
+        .child(Button::new("Set Private Key", edit_private_key))
+        .child(Button::new("Add Peer", add_peer))
+        .child(Button::new("Remove All Peers", remove_all_peers))
+        .child(DummyView)
+        .child(Button::new(format!("Apply ({} pending)", pending), apply_changes))
+        .child(Button::new("Back", |s| {
+            PENDING_EVENTS.write().clear();
+            main_menu(s);
+        }));
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::horizontal()
+                .child(peer_list.with_name("edit_peers"))
+                .child(DummyView)
+                .child(buttons),
+        )
+        .title(format!("Edit {}", name)),
+    );
+}
+
+//pops a single-field EditView dialog and hands the entered text to `callback`
+fn text_prompt(s: &mut Cursive, title: &str, initial: &str, callback: fn(&mut Cursive, String)) {
+    s.add_layer(
+        Dialog::around(EditView::new().content(initial).with_name("prompt_input"))
+            .title(title)
+            .button("OK", move |s| {
+                let value = s
+                    .call_on_name("prompt_input", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                s.pop_layer();
+                callback(s, value.as_str().to_string());
+            })
+            .button("Cancel", pop),
+    );
+}
+
+fn edit_listen_port(s: &mut Cursive) {
+    text_prompt(s, "Listen Port", "", |s, value| {
+        match value.trim().parse::<u16>() {
+            Ok(port) => {
+                PENDING_EVENTS.write().push(WgSetEvent::ListenPort(port));
+                draw_edit_menu(s);
+            }
+            Err(_) => s.add_layer(
+                Dialog::text("Listen port must be a number between 0 and 65535")
+                    .button("OK", pop),
+            ),
+        }
+    });
+}
+
+fn edit_fwmark(s: &mut Cursive) {
+    text_prompt(s, "Fwmark", "", |s, value| {
+        PENDING_EVENTS
+            .write()
+            .push(WgSetEvent::Fwmark(value.trim().to_string()));
+        draw_edit_menu(s);
+    });
+}
+
+fn edit_private_key(s: &mut Cursive) {
+    text_prompt(s, "Private Key", "", |s, value| {
+        PENDING_EVENTS
+            .write()
+            .push(WgSetEvent::PrivateKey(value.trim().to_string()));
+        draw_edit_menu(s);
+    });
+}
+
+fn add_peer(s: &mut Cursive) {
+    peer_form(s, "Add Peer", "", "", "", "", "");
+}
+
+fn edit_peer(s: &mut Cursive, public_key: &str) {
+    let name = INTERFACES.read().current_interface.clone();
+    let buffer = INTERFACES.read();
+    let interface = buffer.interfaces.get(&name).unwrap();
+    let peer = interface
+        .peers
+        .iter()
+        .find(|p| p.public_key == public_key)
+        .unwrap();
+    let public_key = peer.public_key.clone();
+    let preshared_key = peer.preshared_key.clone().unwrap_or_default();
+    let endpoint = peer.endpoint.clone();
+    let allowed_ips = peer.allowed_ips.clone();
+    let persistent_keepalive = if peer.persistent_keepalive == 0 {
+        String::new()
+    } else {
+        peer.persistent_keepalive.to_string()
+    };
+    let public_key_to_remove = public_key.clone();
+    drop(buffer);
+
+    s.add_layer(
+        Dialog::text(format!("Peer {}", public_key))
+            .button("Edit", move |s| {
+                s.pop_layer();
+                peer_form(
+                    s,
+                    "Edit Peer",
+                    &public_key,
+                    &preshared_key,
+                    &endpoint,
+                    &allowed_ips,
+                    &persistent_keepalive,
+                )
+            })
+            .button("Remove", move |s| {
+                PENDING_EVENTS
+                    .write()
+                    .push(WgSetEvent::RemovePeer(public_key_to_remove.clone()));
+                draw_edit_menu(s);
+            })
+            .button("Back", pop),
+    );
+}
+
+fn remove_all_peers(s: &mut Cursive) {
+    PENDING_EVENTS.write().push(WgSetEvent::RemoveAllPeers);
+    draw_edit_menu(s);
+}
+
+//shared add/edit form: all fields blank is "add", fields prefilled from an
+//existing peer is "edit" (wg set upserts either way)
+fn peer_form(
+    s: &mut Cursive,
+    title: &str,
+    public_key: &str,
+    preshared_key: &str,
+    endpoint: &str,
+    allowed_ips: &str,
+    persistent_keepalive: &str,
+) {
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Public Key"))
+        .child(EditView::new().content(public_key).with_name("peer_pubkey"))
+        .child(TextView::new("Preshared Key (optional)"))
+        .child(EditView::new().content(preshared_key).with_name("peer_psk"))
+        .child(TextView::new("Endpoint"))
+        .child(
+            EditView::new()
+                .content(endpoint)
+                .with_name("peer_endpoint"),
+        )
+        .child(TextView::new("Allowed IPs"))
+        .child(
+            EditView::new()
+                .content(allowed_ips)
+                .with_name("peer_allowed_ips"),
+        )
+        .child(TextView::new("Persistent Keepalive (seconds, blank/0 to disable)"))
+        .child(
+            EditView::new()
+                .content(persistent_keepalive)
+                .with_name("peer_keepalive"),
+        );
+
+    s.add_layer(
+        Dialog::around(layout)
+            .title(title)
+            .button("Save", |s| {
+                let public_key = s
+                    .call_on_name("peer_pubkey", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let preshared_key = s
+                    .call_on_name("peer_psk", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let endpoint = s
+                    .call_on_name("peer_endpoint", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let allowed_ips = s
+                    .call_on_name("peer_allowed_ips", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let persistent_keepalive = s
+                    .call_on_name("peer_keepalive", |v: &mut EditView| v.get_content())
+                    .unwrap();
+
+                if public_key.trim().is_empty() {
+                    s.add_layer(Dialog::text("Public key is required").button("OK", pop));
+                    return;
+                }
+
+                //blank explicitly disables keepalive (sent as 0) rather than
+                //leaving whatever value the peer already had untouched
+                let persistent_keepalive = match persistent_keepalive.trim() {
+                    "" => 0,
+                    value => match value.parse::<u16>() {
+                        Ok(keepalive) => keepalive,
+                        Err(_) => {
+                            s.add_layer(
+                                Dialog::text(
+                                    "Persistent keepalive must be a number between 0 and 65535",
+                                )
+                                .button("OK", pop),
+                            );
+                            return;
+                        }
+                    },
+                };
+
+                let event = WgSetEvent::UpdatePeer {
+                    public_key: public_key.trim().to_string(),
+                    preshared_key: (!preshared_key.trim().is_empty())
+                        .then(|| preshared_key.trim().to_string()),
+                    endpoint: (!endpoint.trim().is_empty()).then(|| endpoint.trim().to_string()),
+                    allowed_ips: (!allowed_ips.trim().is_empty())
+                        .then(|| allowed_ips.trim().to_string()),
+                    persistent_keepalive: Some(persistent_keepalive),
+                };
+                PENDING_EVENTS.write().push(event);
+                s.pop_layer();
+                draw_edit_menu(s);
+            })
+            .button("Cancel", pop),
+    );
+}
+
+//splits a comma-separated field (Address, DNS, ...) into its trimmed parts,
+//dropping anything left blank
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn new_interface_wizard(s: &mut Cursive) {
+    s.pop_layer();
+    *NEW_INTERFACE.write() = NewInterfaceDraft {
+        name: String::new(),
+        listen_port: String::new(),
+        address: String::new(),
+        dns: String::new(),
+        peer_public_key: String::new(),
+        peer_endpoint: String::new(),
+        peer_allowed_ips: String::new(),
+    };
+    wizard_name(s);
+}
+
+fn wizard_name(s: &mut Cursive) {
+    text_prompt(s, "Interface Name", "", |s, value| {
+        let name = value.trim().to_string();
+        if valid_interface_name(&name) {
+            NEW_INTERFACE.write().name = name;
+            wizard_listen_port(s);
+        } else {
+            s.add_layer(
+                Dialog::text(
+                    "Interface name must be 1-15 characters from [A-Za-z0-9_=+.-]",
+                )
+                .button("OK", wizard_name),
+            );
+        }
+    });
+}
+
+fn wizard_listen_port(s: &mut Cursive) {
+    text_prompt(s, "Listen Port", "51820", |s, value| {
+        match value.trim().parse::<u16>() {
+            Ok(port) => {
+                NEW_INTERFACE.write().listen_port = port.to_string();
+                wizard_address(s);
+            }
+            Err(_) => s.add_layer(
+                Dialog::text("Listen port must be a number between 0 and 65535")
+                    .button("OK", wizard_listen_port),
+            ),
+        }
+    });
+}
+
+fn wizard_address(s: &mut Cursive) {
+    text_prompt(s, "Address (CIDR)", "", |s, value| {
+        NEW_INTERFACE.write().address = value.trim().to_string();
+        wizard_dns(s);
+    });
+}
+
+fn wizard_dns(s: &mut Cursive) {
+    text_prompt(s, "DNS (optional)", "", |s, value| {
+        NEW_INTERFACE.write().dns = value.trim().to_string();
+        wizard_peer_public_key(s);
+    });
+}
+
+fn wizard_peer_public_key(s: &mut Cursive) {
+    text_prompt(s, "First Peer Public Key (optional)", "", |s, value| {
+        NEW_INTERFACE.write().peer_public_key = value.trim().to_string();
+        if NEW_INTERFACE.read().peer_public_key.is_empty() {
+            finish_new_interface(s);
+        } else {
+            wizard_peer_endpoint(s);
+        }
+    });
+}
+
+fn wizard_peer_endpoint(s: &mut Cursive) {
+    text_prompt(s, "Peer Endpoint (optional)", "", |s, value| {
+        NEW_INTERFACE.write().peer_endpoint = value.trim().to_string();
+        wizard_peer_allowed_ips(s);
+    });
+}
+
+fn wizard_peer_allowed_ips(s: &mut Cursive) {
+    text_prompt(s, "Peer Allowed IPs", "0.0.0.0/0", |s, value| {
+        NEW_INTERFACE.write().peer_allowed_ips = value.trim().to_string();
+        finish_new_interface(s);
+    });
+}
+
+//builds the NewInterfaceSpec from the wizard's answers, generates the
+//interface's own keypair, and writes the config file
+fn finish_new_interface(s: &mut Cursive) {
+    let draft = NEW_INTERFACE.read().clone();
+    let keypair = keygen::generate_keypair();
+
+    let peer = if draft.peer_public_key.is_empty() {
+        None
+    } else {
+        Some(NewPeerSpec {
+            public_key: draft.peer_public_key.clone(),
+            endpoint: draft.peer_endpoint.clone(),
+            allowed_ips: draft.peer_allowed_ips.clone(),
+        })
+    };
+
+    let spec = NewInterfaceSpec {
+        name: draft.name.clone(),
+        private_key: keypair.private_key,
+        listen_port: draft.listen_port.parse().unwrap_or(51820),
+        address: split_list(&draft.address),
+        dns: split_list(&draft.dns),
+        peer,
+    };
+
+    match write_interface_conf(&spec) {
+        Ok(()) => {
+            INTERFACES.write().refresh();
+            sample_monitor();
+            s.add_layer(
+                Dialog::text(format!(
+                    "Created {}.\nPublic Key: {}",
+                    draft.name, keypair.public_key
+                ))
+                .button("OK", |s| {
+                    s.pop_layer();
+                    list_connections(s);
+                }),
+            );
+        }
+        Err(err) => {
+            s.add_layer(
+                Dialog::text(err.to_string())
+                    .button("OK", |s| {
+                        s.pop_layer();
+                        main_menu(s);
+                    })
+                    .title("Failed to create interface"),
+            );
+        }
+    }
+}
+
+fn apply_changes(s: &mut Cursive) {
+    let name = INTERFACES.read().current_interface.clone();
+    let events = PENDING_EVENTS.read().clone();
+    let result = INTERFACES.write().apply(&name, &events);
+    PENDING_EVENTS.write().clear();
+
+    match result {
+        Ok(()) => list_connections(s),
+        Err(stderr) => {
+            s.add_layer(
+                Dialog::text(stderr)
+                    .button("OK", pop)
+                    .title(format!("Command output for {}", name)),
+            );
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Instant;
+
+//how many samples to keep per peer per direction; at roughly one sample a
+//second this is a ~30 second window, enough to see a sparkline move
+const HISTORY_LEN: usize = 30;
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Transfer-rate history for a single peer, derived from the deltas of its
+/// cumulative `transfer_rx`/`transfer_tx` counters between refreshes.
+struct PeerHistory {
+    last_sample: Option<(u64, u64, Instant)>,
+    rx_rates: VecDeque<f64>,
+    tx_rates: VecDeque<f64>,
+}
+
+impl PeerHistory {
+    fn new() -> Self {
+        PeerHistory {
+            last_sample: None,
+            rx_rates: VecDeque::with_capacity(HISTORY_LEN),
+            tx_rates: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    fn record(&mut self, transfer_rx: u64, transfer_tx: u64) {
+        let now = Instant::now();
+        if let Some((prev_rx, prev_tx, prev_time)) = self.last_sample {
+            let elapsed = now.duration_since(prev_time).as_secs_f64().max(0.001);
+            push_sample(
+                &mut self.rx_rates,
+                transfer_rx.saturating_sub(prev_rx) as f64 / elapsed,
+            );
+            push_sample(
+                &mut self.tx_rates,
+                transfer_tx.saturating_sub(prev_tx) as f64 / elapsed,
+            );
+        }
+        self.last_sample = Some((transfer_rx, transfer_tx, now));
+    }
+}
+
+fn push_sample(buffer: &mut VecDeque<f64>, sample: f64) {
+    if buffer.len() == HISTORY_LEN {
+        buffer.pop_front();
+    }
+    buffer.push_back(sample);
+}
+
+/// Keeps per-peer throughput history alive across `InterfacesMap::refresh`
+/// calls, which otherwise rebuild the whole interface map from scratch.
+/// Peers are keyed by public key, so history survives a peer moving between
+/// `WgInterface` instances on refresh.
+pub struct Monitor {
+    peers: BTreeMap<String, PeerHistory>,
+}
+
+impl Monitor {
+    pub const fn new() -> Monitor {
+        Monitor {
+            peers: BTreeMap::new(),
+        }
+    }
+
+    /// Folds a fresh `(transfer_rx, transfer_tx)` sample in for `public_key`.
+    /// Call this only where the underlying counters actually came from a
+    /// fresh backend read (`InterfacesMap::refresh`) -- feeding it the same
+    /// cumulative counters at a new `Instant` (e.g. on plain UI redraws)
+    /// produces spurious near-zero rate samples.
+    pub fn record(&mut self, public_key: &str, transfer_rx: u64, transfer_tx: u64) {
+        let history = self
+            .peers
+            .entry(public_key.to_string())
+            .or_insert_with(PeerHistory::new);
+        history.record(transfer_rx, transfer_tx);
+    }
+
+    /// Renders the current sparkline + rate for both directions of
+    /// `public_key`, without folding in a new sample. Safe to call from
+    /// redraws that aren't backed by a fresh refresh.
+    pub fn render(&self, public_key: &str) -> (String, String) {
+        let Some(history) = self.peers.get(public_key) else {
+            return (String::new(), String::new());
+        };
+
+        let rx_line = format!(
+            "{} {}",
+            sparkline(&history.rx_rates),
+            format_rate(history.rx_rates.back().copied().unwrap_or(0.0))
+        );
+        let tx_line = format!(
+            "{} {}",
+            sparkline(&history.tx_rates),
+            format_rate(history.tx_rates.back().copied().unwrap_or(0.0))
+        );
+        (rx_line, tx_line)
+    }
+}
+
+fn sparkline(samples: &VecDeque<f64>) -> String {
+    if samples.is_empty() {
+        return String::new();
+    }
+    let max = samples.iter().cloned().fold(0.0_f64, f64::max);
+    samples
+        .iter()
+        .map(|&sample| {
+            if max <= 0.0 {
+                SPARK_LEVELS[0]
+            } else {
+                let level = ((sample / max) * (SPARK_LEVELS.len() - 1) as f64).round() as usize;
+                SPARK_LEVELS[level.min(SPARK_LEVELS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+/// Formats a bytes/sec rate the way `wg show`'s cumulative transfer is
+/// formatted, scaled up to the nearest unit.
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    const UNITS: [&str; 4] = ["B/s", "KiB/s", "MiB/s", "GiB/s"];
+    let mut value = bytes_per_sec;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Whether a handshake at `time_since` seconds ago still counts as fresh.
+/// When keepalive is configured, allow some slack for missed beats; when
+/// it's off (`persistent_keepalive == 0`), fall back to WireGuard's ~3
+/// minute rekey timeout.
+pub fn handshake_is_fresh(time_since: u64, persistent_keepalive: u16) -> bool {
+    let window = if persistent_keepalive > 0 {
+        persistent_keepalive as u64 * 3
+    } else {
+        180
+    };
+    time_since < window
+}